@@ -1,12 +1,22 @@
 //! HTTP client for communicating with the whirr server.
 
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-/// HTTP client for the whirr server API.
+use crate::error::{Result, WhirrError, WhirrErrorKind};
+use crate::result::JobResult;
+use crate::retry::RetryPolicy;
+
+/// HTTP client for the whirr server API. Cheap to clone: the underlying
+/// `ureq::Agent` is reference-counted, so each worker in a pool can hold
+/// its own handle to the same connection pool.
+#[derive(Clone)]
 pub struct WhirrClient {
     base_url: String,
     agent: ureq::Agent,
+    retry: RetryPolicy,
 }
 
 /// Job data returned from the server.
@@ -54,6 +64,12 @@ struct RenewRequest<'a> {
     lease_seconds: u64,
 }
 
+/// Job release request.
+#[derive(Debug, Serialize)]
+struct ReleaseRequest<'a> {
+    worker_id: &'a str,
+}
+
 /// Job completion request.
 #[derive(Debug, Serialize)]
 struct CompleteRequest<'a> {
@@ -63,10 +79,16 @@ struct CompleteRequest<'a> {
     run_id: Option<&'a str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error_message: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_kind: Option<WhirrErrorKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<&'a JobResult>,
 }
 
 impl WhirrClient {
-    /// Create a new client connecting to the given server URL.
+    /// Create a new client connecting to the given server URL, with the
+    /// default retry policy. Use [`WhirrClient::with_retry_policy`] to
+    /// override it.
     pub fn new(base_url: &str) -> Self {
         let agent = ureq::AgentBuilder::new()
             .timeout_read(Duration::from_secs(30))
@@ -76,16 +98,19 @@ impl WhirrClient {
         Self {
             base_url: base_url.trim_end_matches('/').to_string(),
             agent,
+            retry: RetryPolicy::default(),
         }
     }
 
+    /// Override the retry policy used by idempotent calls.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
     /// Register this worker with the server.
-    pub fn register_worker(
-        &self,
-        worker_id: &str,
-        hostname: &str,
-        gpu_ids: &[u32],
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn register_worker(&self, worker_id: &str, hostname: &str, gpu_ids: &[u32]) -> Result<()> {
         let url = format!("{}/api/v1/workers/register", self.base_url);
         let request = RegisterRequest {
             worker_id,
@@ -93,59 +118,71 @@ impl WhirrClient {
             gpu_ids,
         };
 
-        self.agent
-            .post(&url)
-            .send_json(&request)?;
-
-        Ok(())
+        self.retry.run(None, || {
+            self.agent
+                .post(&url)
+                .send_json(&request)
+                .map_err(WhirrError::from)?;
+            Ok(())
+        })
     }
 
     /// Unregister this worker from the server.
-    pub fn unregister_worker(&self, worker_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn unregister_worker(&self, worker_id: &str) -> Result<()> {
         let url = format!("{}/api/v1/workers/{}/unregister", self.base_url, worker_id);
-        self.agent.post(&url).call()?;
+        self.agent.post(&url).call().map_err(WhirrError::from)?;
         Ok(())
     }
 
     /// Try to claim the next available job.
-    pub fn claim_job(
-        &self,
-        worker_id: &str,
-        lease_seconds: u64,
-    ) -> Result<Option<Job>, Box<dyn std::error::Error>> {
+    pub fn claim_job(&self, worker_id: &str, lease_seconds: u64) -> Result<Option<Job>> {
         let url = format!("{}/api/v1/jobs/claim", self.base_url);
         let request = ClaimRequest {
             worker_id,
             lease_seconds,
         };
 
-        let response: ClaimResponse = self.agent
-            .post(&url)
-            .send_json(&request)?
-            .into_json()?;
-
-        Ok(response.job)
+        self.retry.run(None, || {
+            let response: ClaimResponse = self
+                .agent
+                .post(&url)
+                .send_json(&request)
+                .map_err(WhirrError::from)?
+                .into_json()?;
+            Ok(response.job)
+        })
     }
 
-    /// Renew the lease on a job (heartbeat).
+    /// Renew the lease on a job (heartbeat). `lease_deadline` is the
+    /// instant the *currently held* lease actually expires (anchored to
+    /// the last successful claim/renewal, not to this call), so retries
+    /// are capped at the point the server would have already reassigned
+    /// the job, letting the worker detect a truly lost lease instead of
+    /// retrying past its expiry.
     pub fn renew_lease(
         &self,
         job_id: i64,
         worker_id: &str,
         lease_seconds: u64,
-    ) -> Result<HeartbeatResponse, Box<dyn std::error::Error>> {
+        lease_deadline: Instant,
+    ) -> Result<HeartbeatResponse> {
         let url = format!("{}/api/v1/jobs/{}/heartbeat", self.base_url, job_id);
         let request = RenewRequest {
             worker_id,
             lease_seconds,
         };
 
-        let response: HeartbeatResponse = self.agent
-            .post(&url)
-            .send_json(&request)?
-            .into_json()?;
-
-        Ok(response)
+        self.retry
+            .run(Some(lease_deadline), || {
+                let response: HeartbeatResponse = self
+                    .agent
+                    .post(&url)
+                    .send_json(&request)
+                    .map_err(WhirrError::from)?
+                    .into_json()?;
+                Ok(response)
+            })
+            .map_err(|e| WhirrError::LeaseLost(Box::new(e)))
     }
 
     /// Report job completion.
@@ -156,19 +193,133 @@ impl WhirrClient {
         exit_code: i32,
         run_id: Option<&str>,
         error_message: Option<&str>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        error_kind: Option<WhirrErrorKind>,
+        result: Option<&JobResult>,
+    ) -> Result<()> {
         let url = format!("{}/api/v1/jobs/{}/complete", self.base_url, job_id);
         let request = CompleteRequest {
             worker_id,
             exit_code,
             run_id,
             error_message,
+            error_kind,
+            result,
         };
 
+        self.retry.run(None, || {
+            self.agent
+                .post(&url)
+                .send_json(&request)
+                .map_err(WhirrError::from)?;
+            Ok(())
+        })
+    }
+
+    /// Release a job back to the queue without completing it, so the
+    /// server returns it to `pending` for another worker to claim. Used
+    /// when this worker is draining in `release` mode instead of killing
+    /// the job outright.
+    pub fn release_job(&self, job_id: i64, worker_id: &str) -> Result<()> {
+        let url = format!("{}/api/v1/jobs/{}/release", self.base_url, job_id);
+        let request = ReleaseRequest { worker_id };
+
+        self.retry.run(None, || {
+            self.agent
+                .post(&url)
+                .send_json(&request)
+                .map_err(WhirrError::from)?;
+            Ok(())
+        })
+    }
+
+    /// Append bytes to a job's live log, starting at `offset` (the byte
+    /// position in the server's copy of the log). Safe to retry: the
+    /// server treats a chunk starting before its current cursor as
+    /// already applied.
+    pub fn append_log(&self, job_id: i64, offset: u64, bytes: &[u8]) -> Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        let url = format!("{}/api/v1/jobs/{}/logs?offset={}", self.base_url, job_id, offset);
+
+        self.retry.run(None, || {
+            self.agent
+                .post(&url)
+                .set("Content-Type", "application/octet-stream")
+                .send_bytes(bytes)
+                .map_err(WhirrError::from)?;
+            Ok(())
+        })
+    }
+
+    /// Upload every regular file directly inside `artifacts_dir` as a
+    /// single `multipart/form-data` request; subdirectories are not
+    /// descended into. A missing directory is not an error (nothing to
+    /// upload).
+    pub fn upload_artifacts(&self, job_id: i64, artifacts_dir: &Path) -> Result<()> {
+        let entries = match std::fs::read_dir(artifacts_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut files = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        let boundary = format!("whirr-{job_id}-boundary");
+        let mut body = Vec::new();
+        for path in &files {
+            let filename = escape_quoted_string(&path.file_name().unwrap_or_default().to_string_lossy());
+            let contents = std::fs::read(path)?;
+
+            body.extend_from_slice(
+                format!(
+                    "--{boundary}\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\n\
+                     Content-Type: application/octet-stream\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(&contents);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+        let url = format!("{}/api/v1/jobs/{}/artifacts", self.base_url, job_id);
         self.agent
             .post(&url)
-            .send_json(&request)?;
+            .set("Content-Type", &format!("multipart/form-data; boundary={boundary}"))
+            .send_bytes(&body)
+            .map_err(WhirrError::from)?;
 
         Ok(())
     }
 }
+
+/// Escape a filename for use as a `multipart/form-data` quoted-string
+/// parameter (RFC 7578 / RFC 6266): backslash-escape `"` and `\`, and drop
+/// bare CR/LF, so an artifact named with an embedded quote or newline can't
+/// corrupt the multipart framing or inject headers.
+fn escape_quoted_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\r' | '\n' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
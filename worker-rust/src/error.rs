@@ -0,0 +1,114 @@
+//! Error types for the whirr worker.
+
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Errors that can occur while registering, claiming, running, or reporting
+/// a job.
+#[derive(Debug, Error)]
+pub enum WhirrError {
+    /// The request itself could not be completed (connection refused, DNS
+    /// failure, timed out, etc).
+    #[error("http request failed: {0}")]
+    Http(ureq::Error),
+
+    /// The server responded, but with a non-2xx status.
+    #[error("server returned {status}: {body}")]
+    Server { status: u16, body: String },
+
+    /// Spawning the job's child process failed.
+    #[error("failed to spawn job: {0}")]
+    JobSpawn(io::Error),
+
+    /// The job's command line was empty.
+    #[error("command is empty")]
+    EmptyCommand,
+
+    /// Renewing the lease on a job failed: the retries ran out, the error
+    /// was not transient, or the retry deadline (anchored to the lease's
+    /// actual expiry) passed before a renewal succeeded. Carries the
+    /// renewal failure that triggered it for diagnostics.
+    #[error("lease lost: {0}")]
+    LeaseLost(Box<WhirrError>),
+
+    /// Any other I/O failure (log files, run directories, response bodies).
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, WhirrError>;
+
+/// Coarse-grained category of a [`WhirrError`], cheap to compare and safe to
+/// send over the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WhirrErrorKind {
+    Http,
+    Server,
+    JobSpawn,
+    EmptyCommand,
+    LeaseLost,
+    Io,
+}
+
+impl WhirrError {
+    /// The coarse-grained category of this error.
+    pub const fn kind(&self) -> WhirrErrorKind {
+        match self {
+            Self::Http(_) => WhirrErrorKind::Http,
+            Self::Server { .. } => WhirrErrorKind::Server,
+            Self::JobSpawn(_) => WhirrErrorKind::JobSpawn,
+            Self::EmptyCommand => WhirrErrorKind::EmptyCommand,
+            Self::LeaseLost(_) => WhirrErrorKind::LeaseLost,
+            Self::Io(_) => WhirrErrorKind::Io,
+        }
+    }
+
+    /// Whether retrying the request that produced this error stands a
+    /// chance of succeeding: connection-level failures and 5xx/429
+    /// responses are transient, everything else is not.
+    pub const fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Http(_) | Self::Server { status: 429, .. } | Self::Server { status: 500..=599, .. }
+        )
+    }
+}
+
+impl From<ureq::Error> for WhirrError {
+    /// Splits ureq's status-code responses into [`WhirrError::Server`] so
+    /// callers can branch on status, and treats everything else (DNS,
+    /// connect, timeout) as [`WhirrError::Http`].
+    fn from(err: ureq::Error) -> Self {
+        match err {
+            ureq::Error::Status(status, response) => Self::Server {
+                status,
+                body: response
+                    .into_string()
+                    .unwrap_or_else(|_| "<unreadable body>".to_string()),
+            },
+            err @ ureq::Error::Transport(_) => Self::Http(err),
+        }
+    }
+}
+
+/// JSON-serializable summary of a [`WhirrError`]. Its `message` is sent to
+/// the server as `error_message` and its `kind` as `error_kind`, so the
+/// server can distinguish transient failures from hard ones without
+/// parsing a display string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorReport {
+    pub kind: WhirrErrorKind,
+    pub message: String,
+}
+
+impl From<&WhirrError> for ErrorReport {
+    fn from(err: &WhirrError) -> Self {
+        Self {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
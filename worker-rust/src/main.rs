@@ -4,19 +4,23 @@
 //! Designed for minimal memory footprint on GPU machines.
 
 mod client;
+mod error;
+mod result;
+mod retry;
 mod runner;
+mod worker;
 
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use std::{env, thread};
 
 use clap::Parser;
 use log::{error, info, warn};
 
 use client::WhirrClient;
-use runner::JobRunner;
+use retry::RetryPolicy;
+use worker::{DrainMode, WorkerManager};
 
 /// Lightweight worker for whirr GPU job orchestration
 #[derive(Parser, Debug)]
@@ -31,9 +35,11 @@ struct Args {
     #[arg(short, long, env = "WHIRR_DATA_DIR")]
     data_dir: PathBuf,
 
-    /// GPU index to use
-    #[arg(short, long)]
-    gpu: Option<u32>,
+    /// Comma-separated GPU indices to run one worker per GPU on (e.g.
+    /// `0,1,2,3`). If omitted, auto-detects via `nvidia-smi`; if none are
+    /// found or detection fails, runs a single CPU-only worker.
+    #[arg(long, value_delimiter = ',')]
+    gpus: Option<Vec<u32>>,
 
     /// Poll interval in seconds
     #[arg(long, default_value = "5")]
@@ -46,6 +52,26 @@ struct Args {
     /// Lease duration in seconds
     #[arg(long, default_value = "60")]
     lease_seconds: u64,
+
+    /// Maximum attempts for idempotent server requests before giving up
+    #[arg(long, default_value = "5")]
+    max_retries: u32,
+
+    /// Base delay before the first retry, in milliseconds (doubles each
+    /// attempt, capped at `--retry-max-delay-ms`; the actual sleep is
+    /// sampled uniformly between zero and that delay)
+    #[arg(long, default_value = "200")]
+    retry_base_delay_ms: u64,
+
+    /// Maximum delay between retries, in milliseconds
+    #[arg(long, default_value = "30000")]
+    retry_max_delay_ms: u64,
+
+    /// What to do with an in-flight job when shutting down: `kill` it and
+    /// report failure, or `release` it back to the queue for another
+    /// worker to reclaim
+    #[arg(long, value_enum, default_value = "kill")]
+    drain_mode: DrainMode,
 }
 
 fn main() {
@@ -54,22 +80,18 @@ fn main() {
 
     let args = Args::parse();
 
-    // Set CUDA_VISIBLE_DEVICES if GPU specified
-    if let Some(gpu) = args.gpu {
-        env::set_var("CUDA_VISIBLE_DEVICES", gpu.to_string());
-        info!("Set CUDA_VISIBLE_DEVICES={}", gpu);
-    }
+    let gpu_ids = args.gpus.unwrap_or_else(worker::detect_gpus);
 
-    // Generate worker ID
     let hostname = gethostname();
-    let worker_id = match args.gpu {
-        Some(gpu) => format!("{}:gpu{}", hostname, gpu),
-        None => format!("{}:default", hostname),
-    };
 
-    info!("Starting whirr-worker: {}", worker_id);
+    info!("Starting whirr-worker on: {}", hostname);
     info!("Server: {}", args.server);
     info!("Data directory: {}", args.data_dir.display());
+    if gpu_ids.is_empty() {
+        info!("No GPUs detected or configured; running a single CPU-only worker");
+    } else {
+        info!("Workers for GPUs: {:?}", gpu_ids);
+    }
 
     // Ensure data directory exists
     let runs_dir = args.data_dir.join("runs");
@@ -78,7 +100,7 @@ fn main() {
         std::process::exit(1);
     }
 
-    // Setup shutdown signal
+    // Setup shutdown signal, shared by every worker in the pool.
     let shutdown = Arc::new(AtomicBool::new(false));
     let shutdown_clone = shutdown.clone();
 
@@ -88,159 +110,29 @@ fn main() {
             warn!("Force shutdown requested");
             std::process::exit(1);
         }
-        info!("Shutdown requested, finishing current job...");
+        info!("Shutdown requested, draining in-flight jobs...");
         shutdown_clone.store(true, Ordering::SeqCst);
     })
     .expect("Failed to set Ctrl+C handler");
 
-    // Create client
-    let client = WhirrClient::new(&args.server);
-
-    // Register with server
-    let gpu_ids = args.gpu.map(|g| vec![g]).unwrap_or_default();
-    if let Err(e) = client.register_worker(&worker_id, &hostname, &gpu_ids) {
-        error!("Failed to register with server: {}", e);
-        std::process::exit(1);
-    }
-    info!("Registered with server");
-
-    // Main worker loop
-    let result = worker_loop(
-        &client,
-        &worker_id,
-        &runs_dir,
-        &shutdown,
+    let client = WhirrClient::new(&args.server).with_retry_policy(RetryPolicy::new(
+        args.max_retries,
+        Duration::from_millis(args.retry_base_delay_ms),
+        Duration::from_millis(args.retry_max_delay_ms),
+    ));
+    let manager = WorkerManager::new(
+        client,
+        hostname,
+        runs_dir,
         Duration::from_secs(args.poll_interval),
         Duration::from_secs(args.heartbeat_interval),
         args.lease_seconds,
+        args.drain_mode,
     );
 
-    // Unregister on exit
-    if let Err(e) = client.unregister_worker(&worker_id) {
-        warn!("Failed to unregister: {}", e);
-    }
-
-    if let Err(e) = result {
-        error!("Worker error: {}", e);
-        std::process::exit(1);
-    }
-
-    info!("Worker stopped");
-}
-
-fn worker_loop(
-    client: &WhirrClient,
-    worker_id: &str,
-    runs_dir: &PathBuf,
-    shutdown: &Arc<AtomicBool>,
-    poll_interval: Duration,
-    heartbeat_interval: Duration,
-    lease_seconds: u64,
-) -> Result<(), Box<dyn std::error::Error>> {
-    while !shutdown.load(Ordering::SeqCst) {
-        // Try to claim a job
-        let job = match client.claim_job(worker_id, lease_seconds) {
-            Ok(job) => job,
-            Err(e) => {
-                warn!("Failed to claim job: {}", e);
-                thread::sleep(poll_interval);
-                continue;
-            }
-        };
-
-        let job = match job {
-            Some(j) => j,
-            None => {
-                // No jobs available
-                thread::sleep(poll_interval);
-                continue;
-            }
-        };
-
-        let job_id = job.id;
-        info!("Claimed job #{}: {}", job_id, job.name.as_deref().unwrap_or(&job.command_argv[0]));
-
-        // Create run directory
-        let run_id = format!("job-{}", job_id);
-        let run_dir = runs_dir.join(&run_id);
-        std::fs::create_dir_all(&run_dir)?;
-        std::fs::create_dir_all(run_dir.join("artifacts"))?;
-
-        // Start job
-        let mut runner = JobRunner::new(
-            job.command_argv.clone(),
-            PathBuf::from(&job.workdir),
-            run_dir.clone(),
-            job_id,
-            run_id.clone(),
-        );
-
-        if let Err(e) = runner.start() {
-            error!("Failed to start job: {}", e);
-            client.complete_job(job_id, worker_id, 1, Some(&run_id), Some(&e.to_string()))?;
-            continue;
-        }
-
-        // Heartbeat loop while job is running
-        let mut last_heartbeat = std::time::Instant::now();
-        let mut cancel_requested = false;
-
-        let exit_code = loop {
-            // Check if job is done
-            if let Some(code) = runner.try_wait()? {
-                break code;
-            }
-
-            // Check for shutdown or cancellation
-            if shutdown.load(Ordering::SeqCst) || cancel_requested {
-                let reason = if shutdown.load(Ordering::SeqCst) { "shutdown" } else { "cancelled" };
-                warn!("Killing job ({})...", reason);
-                let code = runner.kill()?;
-                break code;
-            }
-
-            // Send heartbeat if needed
-            if last_heartbeat.elapsed() >= heartbeat_interval {
-                match client.renew_lease(job_id, worker_id, lease_seconds) {
-                    Ok(response) => {
-                        if response.cancel_requested {
-                            cancel_requested = true;
-                        }
-                    }
-                    Err(e) => {
-                        warn!("Heartbeat failed: {}", e);
-                    }
-                }
-                last_heartbeat = std::time::Instant::now();
-            }
-
-            thread::sleep(Duration::from_millis(500));
-        };
-
-        // Report completion
-        let error_message = if exit_code != 0 {
-            Some(format!("Exit code: {}", exit_code))
-        } else {
-            None
-        };
-
-        if let Err(e) = client.complete_job(job_id, worker_id, exit_code, Some(&run_id), error_message.as_deref()) {
-            warn!("Failed to report completion: {}", e);
-        }
-
-        if exit_code == 0 {
-            info!("Job #{} completed", job_id);
-        } else {
-            warn!("Job #{} failed (exit code: {})", job_id, exit_code);
-        }
-
-        // Exit loop if shutdown requested
-        if shutdown.load(Ordering::SeqCst) {
-            break;
-        }
-    }
+    manager.run(&gpu_ids, &shutdown);
 
-    Ok(())
+    info!("Worker pool stopped");
 }
 
 fn gethostname() -> String {
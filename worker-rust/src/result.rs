@@ -0,0 +1,110 @@
+//! Rich job outcomes: timing, termination reason, and GPU usage sampling.
+
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Why the worker itself killed a job, as opposed to the job's own process
+/// exiting or dying to a signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KillReason {
+    /// The server requested cancellation.
+    Cancel,
+    /// The worker is shutting down.
+    Shutdown,
+    /// Renewing the lease failed (see `WhirrError::LeaseLost`).
+    Lease,
+}
+
+/// How a job's process stopped.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Termination {
+    /// Exited on its own.
+    Exited,
+    /// Died to a signal the worker did not send (segfault, OOM killer,
+    /// etc). Unix only.
+    Signaled { signal: i32 },
+    /// The worker killed it.
+    Killed { reason: KillReason },
+}
+
+/// Full outcome of a finished job, forwarded to the server alongside
+/// `exit_code` so it has more to go on than a bare number.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobResult {
+    pub exit_code: i32,
+    pub termination: Termination,
+    pub duration: Duration,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_gpu_memory_mb: Option<u64>,
+    pub log_bytes: u64,
+}
+
+/// Samples `nvidia-smi` once a second on a background thread for as long
+/// as it's alive, tracking the peak memory used on one GPU. A no-op (always
+/// reports `None`) when no GPU is assigned or `nvidia-smi` isn't present.
+pub struct GpuSampler {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<Option<u64>>>,
+}
+
+impl GpuSampler {
+    pub fn start(gpu_id: Option<u32>) -> Self {
+        let Some(gpu_id) = gpu_id else {
+            return Self {
+                stop: Arc::new(AtomicBool::new(true)),
+                handle: None,
+            };
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            let mut peak_mb = None;
+            while !stop_clone.load(Ordering::Relaxed) {
+                if let Some(mb) = query_memory_used_mb(gpu_id) {
+                    peak_mb = Some(peak_mb.unwrap_or(0).max(mb));
+                }
+                thread::sleep(Duration::from_secs(1));
+            }
+            peak_mb
+        });
+
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// Stop sampling and return the observed peak memory in MB, if any.
+    pub fn stop(self) -> Option<u64> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.and_then(|h| h.join().ok().flatten())
+    }
+}
+
+/// Query `memory.used` for one GPU via `nvidia-smi --query-gpu`. Returns
+/// `None` if `nvidia-smi` is missing, the GPU index isn't found, or the
+/// output can't be parsed.
+fn query_memory_used_mb(gpu_id: u32) -> Option<u64> {
+    let output = Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=memory.used",
+            "--format=csv,noheader,nounits",
+            &format!("--id={gpu_id}"),
+        ])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
@@ -0,0 +1,79 @@
+//! Exponential backoff with full jitter for retrying transient requests.
+
+use std::time::{Duration, Instant};
+
+use log::debug;
+use rand::Rng;
+
+use crate::error::{Result, WhirrError};
+
+/// Retry budget for idempotent `WhirrClient` calls.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub const fn new(max_attempts: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            max_delay,
+        }
+    }
+
+    /// Run `op`, retrying on transient [`WhirrError`]s with exponential
+    /// backoff and full jitter: the sleep before each retry is sampled
+    /// uniformly from `[0, min(max_delay, base_delay * 2^attempt)]` so a
+    /// server restart doesn't get hit by every worker's retry at once.
+    ///
+    /// If `deadline` is set, no retry is attempted once it has passed —
+    /// used by `renew_lease` to keep total retry time under the lease
+    /// duration, so a truly lost lease is detected rather than retried
+    /// past expiry.
+    pub fn run<T>(&self, deadline: Option<Instant>, mut op: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match op() {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt + 1 >= self.max_attempts || !e.is_transient() => return Err(e),
+                Err(e) => {
+                    if let Some(deadline) = deadline {
+                        if Instant::now() >= deadline {
+                            return Err(e);
+                        }
+                    }
+
+                    let delay = self.jittered_delay(attempt);
+                    let delay = match deadline {
+                        Some(deadline) => delay.min(deadline.saturating_duration_since(Instant::now())),
+                        None => delay,
+                    };
+
+                    debug!("retrying after {:?} ({})", delay, e);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn jittered_delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.checked_pow(attempt).unwrap_or(u32::MAX);
+        let computed = self.base_delay.saturating_mul(factor).min(self.max_delay);
+
+        let millis = computed.as_millis() as u64;
+        if millis == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(5, Duration::from_millis(200), Duration::from_secs(30))
+    }
+}
@@ -4,17 +4,23 @@
 //! capturing output to log files, and setting up the job environment.
 
 use std::fs::File;
-use std::path::PathBuf;
-use std::process::{Child, Command, Stdio};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use log::debug;
 
 #[cfg(unix)]
 use std::os::unix::process::CommandExt;
 #[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+#[cfg(unix)]
 use nix::unistd::Pid;
 
+use crate::error::{Result, WhirrError};
+
 /// Manages the execution of a single job.
 pub struct JobRunner {
     command_argv: Vec<String>,
@@ -22,19 +28,30 @@ pub struct JobRunner {
     run_dir: PathBuf,
     job_id: i64,
     run_id: String,
+    gpu_id: Option<u32>,
     child: Option<Child>,
     #[cfg(unix)]
     pgid: Option<i32>,
+    log_path: Option<PathBuf>,
+    log_offset: u64,
+    start_time: Option<Instant>,
+    end_time: Option<Instant>,
+    exit_status: Option<ExitStatus>,
+    detached: bool,
 }
 
 impl JobRunner {
-    /// Create a new job runner.
+    /// Create a new job runner. `gpu_id`, if set, is exported to the child
+    /// as `CUDA_VISIBLE_DEVICES` so a multi-GPU worker pool can pin each
+    /// job to its own worker's device without touching the parent
+    /// process's environment.
     pub const fn new(
         command_argv: Vec<String>,
         workdir: PathBuf,
         run_dir: PathBuf,
         job_id: i64,
         run_id: String,
+        gpu_id: Option<u32>,
     ) -> Self {
         Self {
             command_argv,
@@ -42,21 +59,29 @@ impl JobRunner {
             run_dir,
             job_id,
             run_id,
+            gpu_id,
             child: None,
             #[cfg(unix)]
             pgid: None,
+            log_path: None,
+            log_offset: 0,
+            start_time: None,
+            end_time: None,
+            exit_status: None,
+            detached: false,
         }
     }
 
     /// Start the job process.
-    pub fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn start(&mut self) -> Result<()> {
         if self.command_argv.is_empty() {
-            return Err("Empty command".into());
+            return Err(WhirrError::EmptyCommand);
         }
 
         let log_path = self.run_dir.join("output.log");
-        let log_file = File::create(&log_path)?;
-        let log_file_stderr = log_file.try_clone()?;
+        let log_file = File::create(&log_path).map_err(WhirrError::JobSpawn)?;
+        let log_file_stderr = log_file.try_clone().map_err(WhirrError::JobSpawn)?;
+        self.log_path = Some(log_path.clone());
 
         debug!("Starting job in {workdir}", workdir = self.workdir.display());
         debug!("Command: {command_argv:?}", command_argv = &self.command_argv);
@@ -71,35 +96,106 @@ impl JobRunner {
             .env("WHIRR_RUN_DIR", self.run_dir.to_string_lossy().to_string())
             .env("WHIRR_RUN_ID", &self.run_id);
 
+        if let Some(gpu_id) = self.gpu_id {
+            cmd.env("CUDA_VISIBLE_DEVICES", gpu_id.to_string());
+        }
+
         // On Unix, create a new process group for clean termination.
         #[cfg(unix)]
         {
             cmd.process_group(0);
         }
 
-        let child = cmd.spawn()?;
+        let child = cmd.spawn().map_err(WhirrError::JobSpawn)?;
 
         #[cfg(unix)]
         {
-            self.pgid = Some(i32::try_from(child.id())?);
+            self.pgid = Some(
+                i32::try_from(child.id())
+                    .map_err(|_| WhirrError::Io(io::Error::new(io::ErrorKind::Other, "pid overflow")))?,
+            );
         }
 
         self.child = Some(child);
+        self.start_time = Some(Instant::now());
         Ok(())
     }
 
     /// Check if the job has finished without blocking.
     /// Returns `Some(exit_code)` if finished, `None` if still running.
-    pub fn try_wait(&mut self) -> Result<Option<i32>, Box<dyn std::error::Error>> {
-        let child = self.child.as_mut().ok_or("Job not started")?;
+    pub fn try_wait(&mut self) -> Result<Option<i32>> {
+        let child = self
+            .child
+            .as_mut()
+            .ok_or_else(|| WhirrError::Io(io::Error::new(io::ErrorKind::Other, "job not started")))?;
+
+        match child.try_wait()? {
+            Some(status) => Ok(Some(self.record_exit(status))),
+            None => Ok(None),
+        }
+    }
 
-        Ok(child
-            .try_wait()?
-            .map(|status| status.code().unwrap_or(-1)))
+    /// Wall-clock time from `start()` to the process exiting, once known.
+    pub fn duration(&self) -> Option<Duration> {
+        Some(self.end_time?.saturating_duration_since(self.start_time?))
+    }
+
+    /// Bytes of the job's log confirmed so far via
+    /// [`JobRunner::advance_log_offset`] (equal to the log's full size once
+    /// a final flush after the job exits has been applied by the server).
+    pub fn log_bytes(&self) -> u64 {
+        self.log_offset
+    }
+
+    /// The signal that killed the process, if it died to one, including a
+    /// signal sent by [`JobRunner::kill`] (Unix only; `None` on a normal
+    /// exit).
+    #[cfg(unix)]
+    pub fn exit_signal(&self) -> Option<i32> {
+        self.exit_status?.signal()
+    }
+
+    fn record_exit(&mut self, status: ExitStatus) -> i32 {
+        self.end_time = Some(Instant::now());
+        self.exit_status = Some(status);
+        status.code().unwrap_or(-1)
+    }
+
+    /// Path to this job's live stdout/stderr log, once started.
+    pub fn log_path(&self) -> Option<&Path> {
+        self.log_path.as_deref()
+    }
+
+    /// Read any log bytes written since the last flush, without advancing
+    /// the internal offset. Returns the byte offset the chunk starts at
+    /// (so the caller can forward it to the server's append cursor)
+    /// together with the new bytes; the bytes are empty if nothing
+    /// changed or the job hasn't started. Call
+    /// [`JobRunner::advance_log_offset`] once the chunk has actually been
+    /// applied by the server, so a failed upload is retried instead of
+    /// silently skipped on the next flush.
+    pub fn read_new_log_bytes(&mut self) -> Result<(u64, Vec<u8>)> {
+        let Some(log_path) = &self.log_path else {
+            return Ok((self.log_offset, Vec::new()));
+        };
+
+        let mut file = File::open(log_path)?;
+        file.seek(SeekFrom::Start(self.log_offset))?;
+
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+
+        Ok((self.log_offset, buf))
+    }
+
+    /// Advance the log offset past a chunk that the server has confirmed
+    /// applying, so the next `read_new_log_bytes` starts after it.
+    pub fn advance_log_offset(&mut self, new_offset: u64) {
+        self.log_offset = new_offset;
     }
 
     /// Kill the job and all its children.
-    pub fn kill(&mut self) -> Result<i32, Box<dyn std::error::Error>> {
+    pub fn kill(&mut self) -> Result<i32> {
         #[cfg(unix)]
         {
             if let Some(pgid) = self.pgid {
@@ -115,7 +211,7 @@ impl JobRunner {
                 // Check if still running
                 if let Some(child) = &mut self.child {
                     if let Some(status) = child.try_wait()? {
-                        return Ok(status.code().unwrap_or(-1));
+                        return Ok(self.record_exit(status));
                     }
                     // Still running, send SIGKILL
                     debug!("Sending SIGKILL to process group {pgid}");
@@ -128,17 +224,28 @@ impl JobRunner {
         if let Some(child) = &mut self.child {
             let _ = child.kill();
             let status = child.wait()?;
-            return Ok(status.code().unwrap_or(-1));
+            return Ok(self.record_exit(status));
         }
 
         Ok(-1)
     }
+
+    /// Disable the kill-on-drop behavior and abandon the process group:
+    /// the child keeps running, reparented to init, after this `JobRunner`
+    /// (and the worker process that owns it) goes away. Used by
+    /// `--drain-mode release` so an in-flight job survives a graceful
+    /// worker shutdown for another worker to reclaim once the job is back
+    /// in the queue (see `WhirrClient::release_job`).
+    pub fn detach(&mut self) {
+        self.detached = true;
+    }
 }
 
 impl Drop for JobRunner {
     fn drop(&mut self) {
-        // Ensure we clean up the process on drop
-        if self.child.is_some() {
+        // Ensure we clean up the process on drop, unless it was detached
+        // to survive us.
+        if !self.detached && self.child.is_some() {
             let _ = self.kill();
         }
     }
@@ -0,0 +1,415 @@
+//! Multi-GPU worker pool.
+//!
+//! A single process can host one logical [`Worker`] per GPU, each claiming,
+//! running, and heartbeating jobs independently so that one stuck job on
+//! one GPU can't block the others. [`WorkerManager`] owns registration and
+//! coordinates a shared shutdown signal across the pool.
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use log::{error, info, warn};
+
+use crate::client::WhirrClient;
+use crate::result::{GpuSampler, JobResult, KillReason, Termination};
+use crate::runner::JobRunner;
+
+/// What a worker should do with an in-flight job when asked to shut down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "kebab-case")]
+pub enum DrainMode {
+    /// Kill the job's process group (SIGTERM, then SIGKILL) and report it
+    /// as failed.
+    Kill,
+    /// Detach from the job's process group without signaling it, and ask
+    /// the server to release the job back to `pending` so a fresh worker
+    /// can reclaim it.
+    Release,
+}
+
+/// A single claim/run/heartbeat loop, independent of any other `Worker` in
+/// the pool.
+pub trait Worker: Send {
+    /// Run until `shutdown` is set or a fatal error occurs. Errors are
+    /// logged internally so that one worker's failure doesn't take down
+    /// the rest of the pool.
+    fn run(&self, shutdown: &AtomicBool);
+}
+
+/// A [`Worker`] pinned to a single GPU (or none, for CPU-only jobs).
+pub struct GpuWorker {
+    client: Arc<WhirrClient>,
+    worker_id: String,
+    gpu_id: Option<u32>,
+    runs_dir: PathBuf,
+    poll_interval: Duration,
+    heartbeat_interval: Duration,
+    lease_seconds: u64,
+    drain_mode: DrainMode,
+}
+
+impl GpuWorker {
+    fn new(
+        client: Arc<WhirrClient>,
+        worker_id: String,
+        gpu_id: Option<u32>,
+        runs_dir: PathBuf,
+        poll_interval: Duration,
+        heartbeat_interval: Duration,
+        lease_seconds: u64,
+        drain_mode: DrainMode,
+    ) -> Self {
+        Self {
+            client,
+            worker_id,
+            gpu_id,
+            runs_dir,
+            poll_interval,
+            heartbeat_interval,
+            lease_seconds,
+            drain_mode,
+        }
+    }
+}
+
+impl GpuWorker {
+    /// Forward any log bytes written since the last flush to the server.
+    /// The per-GPU thread driving this worker doubles as the "reader
+    /// thread" tailing the job's output, polled on every heartbeat tick
+    /// rather than on a separate thread per job.
+    fn flush_log(&self, runner: &mut JobRunner, job_id: i64) {
+        match runner.read_new_log_bytes() {
+            Ok((offset, bytes)) if !bytes.is_empty() => {
+                let end = offset + bytes.len() as u64;
+                match self.client.append_log(job_id, offset, &bytes) {
+                    Ok(()) => runner.advance_log_offset(end),
+                    Err(e) => warn!("[{}] failed to stream log: {}", self.worker_id, e),
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("[{}] failed to read log: {}", self.worker_id, e),
+        }
+    }
+}
+
+impl Worker for GpuWorker {
+    fn run(&self, shutdown: &AtomicBool) {
+        while !shutdown.load(Ordering::SeqCst) {
+            let job = match self.client.claim_job(&self.worker_id, self.lease_seconds) {
+                Ok(job) => job,
+                Err(e) if e.is_transient() => {
+                    warn!("[{}] failed to claim job: {}", self.worker_id, e);
+                    thread::sleep(self.poll_interval);
+                    continue;
+                }
+                Err(e) => {
+                    error!("[{}] worker stopped: {}", self.worker_id, e);
+                    return;
+                }
+            };
+
+            let job = match job {
+                Some(j) => j,
+                None => {
+                    thread::sleep(self.poll_interval);
+                    continue;
+                }
+            };
+
+            let job_id = job.id;
+            info!(
+                "[{}] claimed job #{}: {}",
+                self.worker_id,
+                job_id,
+                job.name.as_deref().unwrap_or(&job.command_argv[0])
+            );
+
+            let run_id = format!("job-{}", job_id);
+            let run_dir = self.runs_dir.join(&run_id);
+            if let Err(e) = std::fs::create_dir_all(&run_dir)
+                .and_then(|_| std::fs::create_dir_all(run_dir.join("artifacts")))
+            {
+                error!("[{}] failed to create run directory: {}", self.worker_id, e);
+                continue;
+            }
+
+            let mut runner = JobRunner::new(
+                job.command_argv.clone(),
+                PathBuf::from(&job.workdir),
+                run_dir.clone(),
+                job_id,
+                run_id.clone(),
+                self.gpu_id,
+            );
+
+            if let Err(e) = runner.start() {
+                error!("[{}] failed to start job: {}", self.worker_id, e);
+                let report = crate::error::ErrorReport::from(&e);
+                if let Err(e) = self.client.complete_job(
+                    job_id,
+                    &self.worker_id,
+                    1,
+                    Some(&run_id),
+                    Some(&report.message),
+                    Some(report.kind),
+                    None,
+                ) {
+                    warn!("[{}] failed to report completion: {}", self.worker_id, e);
+                }
+                continue;
+            }
+
+            if let Some(log_path) = runner.log_path() {
+                info!("[{}] streaming log from {}", self.worker_id, log_path.display());
+            }
+
+            let gpu_sampler = GpuSampler::start(self.gpu_id);
+            let mut last_heartbeat = std::time::Instant::now();
+            let mut lease_deadline = last_heartbeat + Duration::from_secs(self.lease_seconds);
+            let mut cancel_requested = false;
+            let mut kill_reason = None;
+
+            let exit_code = loop {
+                match runner.try_wait() {
+                    Ok(Some(code)) => {
+                        // Exited on its own before a pending kill happened.
+                        kill_reason = None;
+                        break code;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!("[{}] failed to poll job: {}", self.worker_id, e);
+                        break 1;
+                    }
+                }
+
+                if shutdown.load(Ordering::SeqCst) && self.drain_mode == DrainMode::Release {
+                    info!(
+                        "[{}] releasing job #{} back to the queue (drain-mode=release)",
+                        self.worker_id, job_id
+                    );
+                    runner.detach();
+                    if let Err(e) = self.client.release_job(job_id, &self.worker_id) {
+                        warn!("[{}] failed to release job: {}", self.worker_id, e);
+                    }
+                    gpu_sampler.stop();
+                    return;
+                }
+
+                if shutdown.load(Ordering::SeqCst) || cancel_requested || kill_reason.is_some() {
+                    let reason = kill_reason.unwrap_or(if shutdown.load(Ordering::SeqCst) {
+                        KillReason::Shutdown
+                    } else {
+                        KillReason::Cancel
+                    });
+                    kill_reason = Some(reason);
+                    warn!("[{}] killing job ({:?})...", self.worker_id, reason);
+                    break runner.kill().unwrap_or(-1);
+                }
+
+                if last_heartbeat.elapsed() >= self.heartbeat_interval {
+                    match self
+                        .client
+                        .renew_lease(job_id, &self.worker_id, self.lease_seconds, lease_deadline)
+                    {
+                        Ok(response) => {
+                            lease_deadline = std::time::Instant::now() + Duration::from_secs(self.lease_seconds);
+                            if response.cancel_requested {
+                                cancel_requested = true;
+                            }
+                        }
+                        Err(e) => {
+                            warn!("[{}] lease lost, killing job: {}", self.worker_id, e);
+                            kill_reason = Some(KillReason::Lease);
+                        }
+                    }
+                    self.flush_log(&mut runner, job_id);
+                    last_heartbeat = std::time::Instant::now();
+                }
+
+                thread::sleep(Duration::from_millis(500));
+            };
+
+            // Final flush so the server's copy of the log is complete
+            // before we report the outcome, then upload any artifacts the
+            // job produced.
+            self.flush_log(&mut runner, job_id);
+            if let Err(e) = self.client.upload_artifacts(job_id, &run_dir.join("artifacts")) {
+                warn!("[{}] failed to upload artifacts: {}", self.worker_id, e);
+            }
+
+            let termination = match kill_reason {
+                Some(reason) => Termination::Killed { reason },
+                #[cfg(unix)]
+                None => match runner.exit_signal() {
+                    Some(signal) => Termination::Signaled { signal },
+                    None => Termination::Exited,
+                },
+                #[cfg(not(unix))]
+                None => Termination::Exited,
+            };
+
+            let result = JobResult {
+                exit_code,
+                termination,
+                duration: runner.duration().unwrap_or_default(),
+                peak_gpu_memory_mb: gpu_sampler.stop(),
+                log_bytes: runner.log_bytes(),
+            };
+
+            let error_message = if exit_code != 0 {
+                Some(format!("Exit code: {}", exit_code))
+            } else {
+                None
+            };
+
+            if let Err(e) = self.client.complete_job(
+                job_id,
+                &self.worker_id,
+                exit_code,
+                Some(&run_id),
+                error_message.as_deref(),
+                None,
+                Some(&result),
+            ) {
+                warn!("[{}] failed to report completion: {}", self.worker_id, e);
+            }
+
+            if exit_code == 0 {
+                info!("[{}] job #{} completed", self.worker_id, job_id);
+            } else {
+                warn!("[{}] job #{} failed (exit code: {})", self.worker_id, job_id, exit_code);
+            }
+
+            if shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+        }
+    }
+}
+
+/// Owns registration and lifecycle for a pool of [`GpuWorker`]s, one per
+/// GPU (or a single default worker if none are given).
+pub struct WorkerManager {
+    client: Arc<WhirrClient>,
+    hostname: String,
+    runs_dir: PathBuf,
+    poll_interval: Duration,
+    heartbeat_interval: Duration,
+    lease_seconds: u64,
+    drain_mode: DrainMode,
+}
+
+impl WorkerManager {
+    pub fn new(
+        client: WhirrClient,
+        hostname: String,
+        runs_dir: PathBuf,
+        poll_interval: Duration,
+        heartbeat_interval: Duration,
+        lease_seconds: u64,
+        drain_mode: DrainMode,
+    ) -> Self {
+        Self {
+            client: Arc::new(client),
+            hostname,
+            runs_dir,
+            poll_interval,
+            heartbeat_interval,
+            lease_seconds,
+            drain_mode,
+        }
+    }
+
+    /// Register one worker per GPU (or a single `:default` worker if
+    /// `gpu_ids` is empty), run the ones that registered successfully to
+    /// completion on their own threads, and unregister them all before
+    /// returning. A worker whose registration fails never starts its
+    /// claim/run/heartbeat loop: without a successful registration, the
+    /// server has no record of it and every claim it made would be
+    /// orphaned.
+    pub fn run(&self, gpu_ids: &[u32], shutdown: &Arc<AtomicBool>) {
+        let workers: Vec<GpuWorker> = if gpu_ids.is_empty() {
+            vec![self.make_worker(None)]
+        } else {
+            gpu_ids.iter().map(|&gpu| self.make_worker(Some(gpu))).collect()
+        };
+
+        let workers: Vec<&GpuWorker> = workers
+            .iter()
+            .filter(|worker| {
+                let gpu_ids = worker.gpu_id.map(|g| vec![g]).unwrap_or_default();
+                match self.client.register_worker(&worker.worker_id, &self.hostname, &gpu_ids) {
+                    Ok(()) => {
+                        info!("[{}] registered with server", worker.worker_id);
+                        true
+                    }
+                    Err(e) => {
+                        error!(
+                            "[{}] failed to register with server, not starting this worker: {}",
+                            worker.worker_id, e
+                        );
+                        false
+                    }
+                }
+            })
+            .collect();
+
+        if workers.is_empty() {
+            error!("no workers registered successfully; nothing to run");
+            return;
+        }
+
+        thread::scope(|scope| {
+            for worker in &workers {
+                let shutdown = Arc::clone(shutdown);
+                scope.spawn(move || worker.run(&shutdown));
+            }
+        });
+
+        for worker in &workers {
+            if let Err(e) = self.client.unregister_worker(&worker.worker_id) {
+                warn!("[{}] failed to unregister: {}", worker.worker_id, e);
+            }
+        }
+    }
+
+    fn make_worker(&self, gpu_id: Option<u32>) -> GpuWorker {
+        let worker_id = match gpu_id {
+            Some(gpu) => format!("{}:gpu{}", self.hostname, gpu),
+            None => format!("{}:default", self.hostname),
+        };
+
+        GpuWorker::new(
+            Arc::clone(&self.client),
+            worker_id,
+            gpu_id,
+            self.runs_dir.clone(),
+            self.poll_interval,
+            self.heartbeat_interval,
+            self.lease_seconds,
+            self.drain_mode,
+        )
+    }
+}
+
+/// Auto-detect available GPUs via `nvidia-smi`. Returns an empty list (a
+/// single CPU-only worker) if `nvidia-smi` isn't present or reports none.
+pub fn detect_gpus() -> Vec<u32> {
+    let output = match Command::new("nvidia-smi")
+        .args(["--query-gpu=index", "--format=csv,noheader"])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect()
+}